@@ -1,270 +1,473 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
-use std::path::{Path, PathBuf};
-use std::io::BufReader;
-use rocket::request::Request;
-use rocket::response::{Response, Responder};
-use rocket::http::{Status, ContentType};
-use std::io::Read;
-use std::io::Result;
 use std::io;
-use rocket::response::NamedFile;
-use std::result;
-use std::io::Cursor;
-use std::usize;
-use std::fmt;
-use std::sync::Arc;
-
-
-#[derive(Debug, Clone)]
-pub struct SizedFile {
-    bytes: Vec<u8>,
-    size: usize
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use priority_queue::PriorityQueue;
+
+use cached_file::CachedFile;
+use error::{CacheInvalidationError, CacheInvalidationSuccess};
+use freshness::Freshness;
+use priority_function::PriorityFunction;
+use sized_file::SizedFile;
+
+/// The data that backs a `Cache`, held behind its own lock so that reading it
+/// back out (to dispatch a response) is quick, independent of how long a
+/// concurrent disk read for a different path takes. The exception is a
+/// freshness-triggered refresh in `get`, which re-reads the file while holding this lock.
+pub(crate) struct CacheInner {
+    pub(crate) size_limit: usize, // The number of bytes the cache is allowed to hold.
+    pub(crate) min_file_size: usize, // The smallest file the cache will store.
+    pub(crate) max_file_size: usize, // The largest file the cache will store.
+    pub(crate) priority_function: PriorityFunction, // Scores a file's (access_count, size_bytes) to decide what to evict first.
+    pub(crate) freshness: Freshness, // How eagerly cached files are checked against the filesystem for changes.
+    pub(crate) mmap_threshold: usize, // Files larger than this many bytes are memory-mapped instead of heap-allocated.
+    pub(crate) size_bytes: usize, // Running total of bytes resident in file_map, kept in sync by insert()/remove().
+    pub(crate) file_map: HashMap<PathBuf, Arc<SizedFile>>, // Holds the files that the cache is caching
+    pub(crate) access_count_map: HashMap<PathBuf, usize>, // Every file that is accessed will have the number of times it is accessed logged in this map.
+    pub(crate) last_checked_map: HashMap<PathBuf, Instant>, // Last time each path was stat'd for `Freshness::Interval`.
+    pub(crate) priority_queue: PriorityQueue<PathBuf, Reverse<usize>>, // Min-priority index of resident files; Reverse so pop() yields the lowest-priority entry.
 }
 
-#[derive(Debug, Clone)]
-pub struct CachedFile {
-    path: PathBuf,
-    file: Arc<SizedFile>
-
+pub struct Cache {
+    pub(crate) inner: Mutex<CacheInner>,
+    loading: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>, // Per-path gate so a burst of misses for the same path reads the file from disk only once, if it gets cached.
 }
 
-//impl fmt::Display for CachedFile {
-//    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//        write!(f, "{{Path: {:?}, Size: {}}}", self.path, self.size)
-//
-//    }
-//}
-
-//impl CachedFile {
-//    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<CachedFile> {
-//        let file = File::open(path.as_ref())?;
-//        let mut reader = BufReader::new(file);
-//        let mut buffer: Vec<u8> = vec!();
-//        let size: usize = reader.read_to_end(&mut buffer)?;
-//
-//        Ok(CachedFile {
-//            path: path.as_ref().to_path_buf(),
-//            bytes: buffer,
-//            size
-//        })
-//    }
-//}
-
-impl SizedFile {
-    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SizedFile> {
-        let file = File::open(path.as_ref())?;
-        let mut reader = BufReader::new(file);
-        let mut buffer: Vec<u8> = vec!();
-        let size: usize = reader.read_to_end(&mut buffer)?;
-
-        Ok(SizedFile {
-            bytes: buffer,
-            size
-        })
+impl Cache {
+    pub(crate) fn from_inner(inner: CacheInner) -> Cache {
+        Cache {
+            inner: Mutex::new(inner),
+            loading: Mutex::new(HashMap::new()),
+        }
     }
-}
 
-/// Streams the named file to the client. Sets or overrides the Content-Type in
-/// the response according to the file's extension if the extension is
-/// recognized. See
-/// [ContentType::from_extension](/rocket/http/struct.ContentType.html#method.from_extension)
-/// for more information. If you would like to stream a file with a different
-/// Content-Type than that implied by its extension, use a `File` directly.
-impl Responder<'static> for CachedFile {
-    fn respond_to(self, _: &Request) -> result::Result<Response<'static>, Status> {
-        let mut response = Response::new();
-        if let Some(ext) = self.path.extension() {
-            if let Some(ct) = ContentType::from_extension(&ext.to_string_lossy()) {
-                response.set_header(ct);
-            }
+    /// Attempt to store a given file in the cache.
+    ///
+    /// If there isn't room, the lowest-priority resident files are popped off the
+    /// priority queue to make space, but only if their combined priority is lower
+    /// than the priority of the file being stored; otherwise they are pushed back.
+    pub fn store(
+        &self,
+        path: PathBuf,
+        file: Arc<SizedFile>,
+    ) -> Result<CacheInvalidationSuccess, CacheInvalidationError> {
+        self.inner.lock().unwrap().store(path, file)
+    }
+
+    /// Increments the access count.
+    /// Gets the file from the cache if it exists, refreshing it first if it's stale.
+    pub fn get(&self, path: &PathBuf) -> Option<CachedFile> {
+        self.inner.lock().unwrap().get(path)
+    }
+
+    /// Either gets the file from the cache, gets it from the filesystem and tries to cache it,
+    /// or fails to find the file and returns None.
+    ///
+    /// Concurrent misses for the same path share a single disk read when the file is
+    /// admitted to the cache: the first caller becomes the loader for `pathbuf` while
+    /// every other caller waits on that path's entry in `loading` and then re-checks
+    /// the cache, which the loader has populated by the time it releases the entry.
+    /// If `store` rejects the file, the loaded copy is not shared: each waiter in turn
+    /// reads the file from disk itself.
+    pub fn get_or_cache(&self, pathbuf: PathBuf) -> Option<CachedFile> {
+        if let Some(cache_file) = self.get(&pathbuf) {
+            info!("Cache hit for file: {:?}", pathbuf);
+            return Some(cache_file);
         }
 
-        let file: *const SizedFile = Arc::into_raw(self.file);
-        unsafe {
-            response.set_streamed_body((*file).bytes.as_slice());
-            let _ = Arc::from_raw(file); // Prevent dangling pointer?
+        info!("Cache missed for file: {:?}", pathbuf);
+
+        let path_lock = {
+            let mut loading = self.loading.lock().unwrap();
+            loading.entry(pathbuf.clone()).or_insert_with(|| Arc::new(Mutex::new(()))).clone()
+        };
+        let _loading_guard = path_lock.lock().unwrap();
+
+        // Someone else may have already loaded this path while we waited for the lock above.
+        // The self.get() miss above already counted this request's access, so re-check via
+        // peek(), which doesn't touch access_count_map/priority_queue, instead of get() again
+        // — calling get() here would be a second increment for the same logical request.
+        if let Some(cache_file) = self.inner.lock().unwrap().peek(&pathbuf) {
+            self.release_loader(&pathbuf, &path_lock);
+            return Some(cache_file);
         }
 
-        Ok(response)
-    }
-}
+        // Instead the file needs to be read from the filesystem.
+        let mmap_threshold = self.inner.lock().unwrap().mmap_threshold;
+        let sized_file = SizedFile::open(pathbuf.as_path(), mmap_threshold);
+        let result = if let Ok(file) = sized_file {
+            // If the file was read, convert it to a cached file and attempt to store it in the cache
+            let arc_file = Arc::new(file);
+            let cached_file: CachedFile = CachedFile {
+                path: pathbuf.clone(),
+                file: arc_file.clone(),
+            };
 
-/// Alternative implementation for sending the file via a reference.
-impl <'a>Responder<'a> for &'a CachedFile {
-    fn respond_to(self, _: &Request) -> result::Result<Response<'a>, Status> {
-        let mut response = Response::new();
-        if let Some(ext) = self.path.extension() {
-            if let Some(ct) = ContentType::from_extension(&ext.to_string_lossy()) {
-                response.set_header(ct);
+            info!("Trying to add file {:?} to cache", pathbuf);
+            if let Err(e) = self.store(pathbuf.clone(), arc_file) {
+                info!("Didn't cache file {:?}: {}", pathbuf, e);
             }
-        }
+            Some(cached_file)
+        } else {
+            // Indicate that the file was not found in either the filesystem or cache.
+            None
+        };
 
-        response.set_streamed_body(self.file.bytes.as_slice());
-        Ok(response)
+        self.release_loader(&pathbuf, &path_lock);
+        result
     }
-}
-
 
+    /// Clears `pathbuf`'s entry in `loading`, but only if it still points at `path_lock`.
+    ///
+    /// A path can cycle through the loader again (a later miss after eviction, or a
+    /// freshness-triggered refresh) while an earlier waiter is still between acquiring
+    /// its now-stale guard and reaching this cleanup; without the `Arc::ptr_eq` check
+    /// that waiter could delete a different, still-in-flight loader's entry and let a
+    /// third request independently re-read and re-store the same path.
+    fn release_loader(&self, pathbuf: &PathBuf, path_lock: &Arc<Mutex<()>>) {
+        let mut loading = self.loading.lock().unwrap();
+        if loading.get(pathbuf).map_or(false, |current| Arc::ptr_eq(current, path_lock)) {
+            loading.remove(pathbuf);
+        }
+    }
 
-pub struct Cache {
-    size_limit: usize, // Currently this is being used as the number of elements in the cache, but should be used as the number of bytes in the hashmap.
-    file_map: HashMap<PathBuf, Arc<SizedFile>>, // Holds the files that the cache is caching
-    access_count_map: HashMap<PathBuf, usize> // Every file that is accessed will have the number of times it is accessed logged in this map.
+    /// Writes an index of the currently resident files (path, size, access count)
+    /// to `path`, so a future `CacheBuilder::restore_from_index` can re-open and
+    /// re-mmap/re-read them, restoring both contents and eviction priorities.
+    pub fn persist_index<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.inner.lock().unwrap().persist_index(path)
+    }
 }
 
-//impl fmt::Display for Cache {
-//    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//        // TODO because the entries are unsorted, it is not guaranteed that the access counts will correspond to the paths.
-//        f.debug_list().entries(
-//            self.file_map.iter().zip(self.access_count_map.iter()).map(|x| {
-//                let size = (x.0).1.size;
-//                let count = (x.1).1;
-//                let path = &(x.0).1.path;
-//
-//                (path, size, count)
-//        })
-//        ).finish()
-//    }
-//}
+impl CacheInner {
+    fn store(
+        &mut self,
+        path: PathBuf,
+        file: Arc<SizedFile>,
+    ) -> Result<CacheInvalidationSuccess, CacheInvalidationError> {
+        let new_size = file.size;
 
-impl Cache {
-
-    pub fn new(size_limit: usize) -> Cache {
-        Cache {
-            size_limit,
-            file_map: HashMap::new(),
-            access_count_map: HashMap::new()
+        if new_size > self.size_limit {
+            return Err(CacheInvalidationError::NewFileLargerThanCache);
+        }
+        if new_size < self.min_file_size {
+            return Err(CacheInvalidationError::NewFileSmallerThanMin);
+        }
+        if new_size > self.max_file_size {
+            return Err(CacheInvalidationError::NewFileLargerThanMax);
         }
-    }
 
-    /// Attempt to store a given file in the the cache.
-    /// Storing will fail if the current files have more access attempts than the file being added.
-    /// If the provided file has more more access attempts than one of the files in the cache,
-    /// but the cache is full, a file will have to be removed from the cache to make room
-    /// for the new file.
-    pub fn store(&mut self, path: PathBuf, file: Arc<SizedFile>) -> result::Result<(), String> {
-
-        // If there is room in the hashmap, just add the file
-        if self.size() < self.size_limit {
-            self.file_map.insert(path.clone(), file);
-            info!("Inserting a file: {:?} into a not-full cache.", path);
-            return Ok(()) // Inserted successfully.
+        if self.size_bytes + new_size <= self.size_limit {
+            self.insert(path.clone(), file);
+            info!("Inserting file: {:?} into available space in the cache.", path);
+            return Ok(CacheInvalidationSuccess::InsertedIntoAvailableSpace);
         }
 
-        match self.lowest_access_count_in_file_map() {
-            Some(lowest) => {
-                let (lowest_count, lowest_key) = lowest;
-                // It should early return if a file can be added without having to remove a file first.
-                let possible_store_count: usize = *self.access_count_map.get(&path).unwrap_or(&0usize);
-                // Currently this removes the file that has been accessed the least.
-                // TODO in the future, this should remove the file that has the lowest "score"
-                if possible_store_count > lowest_count {
-                    self.file_map.remove(&lowest_key);
-                    self.file_map.insert(path.clone(), file);
-                    info!("Removing file: {:?} to make room for file: {:?}.", lowest_key, path);
-                    return Ok(())
-                } else {
-                    info!("File: {:?} has less demand than files already in the cache.", path);
-                    return Err(String::from("File demand for file is lower than files already in the cache"));
+        let new_priority = self.priority_of(&path, new_size);
+
+        let mut freed_bytes = 0usize;
+        let mut evicted_priority = 0usize;
+        let mut popped: Vec<(PathBuf, Reverse<usize>)> = Vec::new();
+
+        while self.size_bytes - freed_bytes + new_size > self.size_limit {
+            match self.priority_queue.pop() {
+                Some((key, Reverse(priority))) => {
+                    freed_bytes += self.file_map.get(&key).map(|f| f.size).unwrap_or(0);
+                    evicted_priority += priority;
+                    popped.push((key, Reverse(priority)));
                 }
+                None => break, // Nothing left to evict.
             }
-            None => {
-                info!("Inserting first file: {:?} into cache.", path);
-                self.file_map.insert(path, file);
-                Ok(())
+        }
+
+        // `new_size <= self.size_limit` was already checked above, so evicting every
+        // resident file always frees enough room; the only way to fail from here is
+        // that it wasn't worth it.
+        debug_assert!(self.size_bytes - freed_bytes + new_size <= self.size_limit);
+
+        if evicted_priority >= new_priority {
+            // Evicting wasn't worth it: put the popped entries back.
+            for (key, priority) in popped {
+                self.priority_queue.push(key, priority);
             }
+            info!("File: {:?} has a lower priority than the files that would need to be evicted to store it.", path);
+            return Err(CacheInvalidationError::NewPriorityIsNotHighEnough);
+        }
+
+        for (key, _) in &popped {
+            self.remove(key);
         }
+        self.insert(path.clone(), file);
+        info!("Evicting {} file(s) to make room for file: {:?}.", popped.len(), path);
+        Ok(CacheInvalidationSuccess::ReplacedFile)
     }
 
-    /// Increments the access count.
-    /// Gets the file from the cache if it exists.
-    pub fn get(&mut self, path: &PathBuf) -> Option<CachedFile> {
-        let count: &mut usize = self.access_count_map.entry(path.to_path_buf()).or_insert(0usize);
-        *count += 1; // Increment the access count
+    fn get(&mut self, path: &PathBuf) -> Option<CachedFile> {
+        if self.file_map.contains_key(path) {
+            self.refresh_if_stale(path);
+        }
+
+        let count: usize = {
+            let count = self.access_count_map.entry(path.to_path_buf()).or_insert(0usize);
+            *count += 1; // Increment the access count
+            *count
+        };
         match self.file_map.get(path) {
             Some(sized_file) => {
-                Some(
-                    CachedFile {
-                        path: path.clone(),
-                        file: sized_file.clone()
-                    }
-                )
+                let priority = (self.priority_function)(count, sized_file.size);
+                self.priority_queue.change_priority(path, Reverse(priority));
+                Some(CachedFile {
+                    path: path.clone(),
+                    file: sized_file.clone(),
+                })
             }
-            None => None
-
+            None => None,
         }
+    }
 
+    /// Looks up `path` without touching `access_count_map` or `priority_queue`. For
+    /// callers that already counted this access elsewhere (`Cache::get_or_cache`'s
+    /// post-lock re-check, after its own top-of-function `get()` miss) and would
+    /// otherwise double-count it by calling `get()` a second time.
+    fn peek(&self, path: &PathBuf) -> Option<CachedFile> {
+        self.file_map.get(path).map(|sized_file| CachedFile {
+            path: path.clone(),
+            file: sized_file.clone(),
+        })
     }
 
-    /// Either gets the file from the cache, gets it from the filesystem and tries to cache it,
-    /// or fails to find the file and returns None.
+    /// Re-reads `path` from disk and replaces its cache entry if its mtime has
+    /// changed, per the configured `Freshness` mode. The accumulated access count
+    /// for `path` is left untouched, so the refreshed file keeps its eviction priority.
+    fn refresh_if_stale(&mut self, path: &PathBuf) {
+        let should_check = match self.freshness {
+            Freshness::Never => false,
+            Freshness::Always => true,
+            Freshness::Interval(interval) => {
+                let now = Instant::now();
+                let due = self
+                    .last_checked_map
+                    .get(path)
+                    .map(|last_checked| now.duration_since(*last_checked) >= interval)
+                    .unwrap_or(true);
+                if due {
+                    self.last_checked_map.insert(path.clone(), now);
+                }
+                due
+            }
+        };
 
-    pub fn get_or_cache(&mut self, pathbuf: PathBuf) -> Option<CachedFile> {
-        // First try to get the file in the cache that corresponds to the desired path.
+        if !should_check {
+            return;
+        }
+
+        let on_disk_modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return, // File vanished or became unreadable; keep serving the cached copy.
+        };
 
-        {
-            if let Some(cache_file) = self.get(&pathbuf) {
-                info!("Cache hit for file: {:?}", pathbuf);
-                return Some(cache_file)
+        let is_stale = self
+            .file_map
+            .get(path)
+            .map(|cached| cached.modified != on_disk_modified)
+            .unwrap_or(false);
+
+        if !is_stale {
+            return;
+        }
+
+        if let Ok(fresh_file) = SizedFile::open(path, self.mmap_threshold) {
+            info!("File: {:?} changed on disk, refreshing the cached copy.", path);
+            let last_checked = self.last_checked_map.get(path).cloned();
+            self.remove(path);
+            self.insert(path.clone(), Arc::new(fresh_file));
+            if let Some(last_checked) = last_checked {
+                self.last_checked_map.insert(path.clone(), last_checked);
             }
         }
+    }
 
-        info!("Cache missed for file: {:?}", pathbuf);
-        // Instead the file needs to read from the filesystem.
-        let sized_file: Result<SizedFile> = SizedFile::open(pathbuf.as_path());
-        // Check if the file read was a success.
-        if let Ok(file) = sized_file {
-            // If the file was read, convert it to a cached file and attempt to store it in the cache
-            let arc_file = Arc::new(file);
-            let cached_file: CachedFile = CachedFile {
-                path: pathbuf.clone(),
-                file: arc_file.clone()
-            };
+    /// Adds a file to the file_map and priority_queue, keeping size_bytes in sync.
+    pub(crate) fn insert(&mut self, path: PathBuf, file: Arc<SizedFile>) {
+        let priority = self.priority_of(&path, file.size);
+        self.size_bytes += file.size;
+        self.priority_queue.push(path.clone(), Reverse(priority));
+        self.file_map.insert(path, file);
+    }
 
-            info!("Trying to add file {:?} to cache", pathbuf);
-            let _ = self.store(pathbuf, arc_file); // possibly stores the cached file in the store.
-            Some(cached_file)
-        } else {
-            // Indicate that the file was not found in either the filesystem or cache.
-            None
+    /// Removes a file from the file_map and priority_queue, keeping size_bytes in sync.
+    fn remove(&mut self, path: &PathBuf) {
+        if let Some(file) = self.file_map.remove(path) {
+            self.size_bytes -= file.size;
         }
+        self.priority_queue.remove(path);
+        self.last_checked_map.remove(path);
     }
 
-    /// Gets the file with the lowest access count in the hashmap.
-    fn lowest_access_count_in_file_map(&self) -> Option<(usize,PathBuf)> {
-        if self.file_map.keys().len() == 0 {
-            return None
+    /// The priority the file at `path` would have, given its size, if it were stored.
+    fn priority_of(&self, path: &PathBuf, size: usize) -> usize {
+        let count = *self.access_count_map.get(path).unwrap_or(&0usize);
+        (self.priority_function)(count, size)
+    }
+
+    /// Writes `<size>\t<access_count>\t<path>` lines for every resident file, ordered
+    /// by descending priority so that if the persisted set doesn't fit on restart,
+    /// `CacheBuilder::restore_from_index`'s budget favors the previously-hottest files.
+    fn persist_index<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut entries: Vec<(&PathBuf, &Arc<SizedFile>, usize)> = self
+            .file_map
+            .iter()
+            .map(|(cached_path, sized_file)| {
+                let count = *self.access_count_map.get(cached_path).unwrap_or(&0usize);
+                let priority = (self.priority_function)(count, sized_file.size);
+                (cached_path, sized_file, priority)
+            })
+            .collect();
+        entries.sort_by_key(|&(_, _, priority)| Reverse(priority));
+
+        let mut index_file = File::create(path)?;
+        for (cached_path, sized_file, _) in entries {
+            let count = *self.access_count_map.get(cached_path).unwrap_or(&0usize);
+            writeln!(index_file, "{}\t{}\t{}", sized_file.size, count, cached_path.display())?;
         }
+        Ok(())
+    }
+}
 
-        let mut lowest_access_count: usize = usize::MAX;
-        let mut lowest_access_key: PathBuf = PathBuf::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use priority_function::default_priority_function;
+    use sized_file::FileBacking;
+    use std::time::SystemTime;
+
+    fn sized_file(size: usize) -> Arc<SizedFile> {
+        Arc::new(SizedFile {
+            backing: FileBacking::Heap(vec![0u8; size]),
+            size,
+            modified: SystemTime::now(),
+        })
+    }
 
-        for file_key in self.file_map.keys() {
-            let access_count: &usize = self.access_count_map.get(file_key).unwrap(); // It is guaranteed for the access count entry to exist if the file_map entry exists.
-            if access_count < &lowest_access_count {
-                lowest_access_count = access_count + 0;
-                lowest_access_key = file_key.clone();
-            }
+    fn inner(size_limit: usize) -> CacheInner {
+        CacheInner {
+            size_limit,
+            min_file_size: 0,
+            max_file_size: usize::max_value(),
+            priority_function: default_priority_function,
+            freshness: Freshness::Never,
+            mmap_threshold: usize::max_value(),
+            size_bytes: 0,
+            file_map: HashMap::new(),
+            access_count_map: HashMap::new(),
+            last_checked_map: HashMap::new(),
+            priority_queue: PriorityQueue::new(),
         }
-        Some((lowest_access_count, lowest_access_key))
     }
 
-    /// Gets the number of files in the file_map.
-    fn size(&self) -> usize {
-        let mut size: usize = 0;
-        for _ in self.file_map.keys() {
-            size += 1;
-        }
-        size
+    #[test]
+    fn store_into_available_space() {
+        let mut cache = inner(100);
+        let result = cache.store(PathBuf::from("a"), sized_file(10));
+        assert_eq!(result, Ok(CacheInvalidationSuccess::InsertedIntoAvailableSpace));
+        assert_eq!(cache.size_bytes, 10);
     }
 
-    /// gets the size of the files in the file_map.
-    fn size_bytes(&self) -> usize {
-//        let mut size: usize = 0;
-        self.file_map.iter().fold(0usize, |size, x| {
-           size +  x.1.size
-        })
+    #[test]
+    fn store_evicts_lower_priority_file() {
+        let mut cache = inner(10);
+        // "cold" has never been accessed, "hot" will arrive with a higher access count.
+        cache.store(PathBuf::from("cold"), sized_file(10)).unwrap();
+        cache.access_count_map.insert(PathBuf::from("hot"), 10);
+
+        let result = cache.store(PathBuf::from("hot"), sized_file(10));
+        assert_eq!(result, Ok(CacheInvalidationSuccess::ReplacedFile));
+        assert!(!cache.file_map.contains_key(&PathBuf::from("cold")));
+        assert!(cache.file_map.contains_key(&PathBuf::from("hot")));
+    }
+
+    #[test]
+    fn store_rejects_low_priority_file_and_keeps_resident_file() {
+        let mut cache = inner(10);
+        cache.access_count_map.insert(PathBuf::from("hot"), 10);
+        cache.store(PathBuf::from("hot"), sized_file(10)).unwrap();
+
+        // "cold" has never been accessed, so it isn't worth evicting "hot" for.
+        let result = cache.store(PathBuf::from("cold"), sized_file(10));
+        assert_eq!(result, Err(CacheInvalidationError::NewPriorityIsNotHighEnough));
+        assert!(cache.file_map.contains_key(&PathBuf::from("hot")));
+        assert!(!cache.file_map.contains_key(&PathBuf::from("cold")));
+    }
+
+    #[test]
+    fn store_rejects_file_larger_than_cache() {
+        let mut cache = inner(10);
+        let result = cache.store(PathBuf::from("a"), sized_file(11));
+        assert_eq!(result, Err(CacheInvalidationError::NewFileLargerThanCache));
+    }
+
+    #[test]
+    fn store_rejects_file_smaller_than_min() {
+        let mut cache = inner(100);
+        cache.min_file_size = 5;
+        let result = cache.store(PathBuf::from("a"), sized_file(1));
+        assert_eq!(result, Err(CacheInvalidationError::NewFileSmallerThanMin));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn store_rejects_file_larger_than_max() {
+        let mut cache = inner(100);
+        cache.max_file_size = 5;
+        let result = cache.store(PathBuf::from("a"), sized_file(10));
+        assert_eq!(result, Err(CacheInvalidationError::NewFileLargerThanMax));
+    }
+
+    #[test]
+    fn remove_prunes_last_checked_map() {
+        let mut cache = inner(100);
+        cache.store(PathBuf::from("a"), sized_file(10)).unwrap();
+        cache.last_checked_map.insert(PathBuf::from("a"), Instant::now());
+
+        cache.remove(&PathBuf::from("a"));
+
+        assert!(!cache.last_checked_map.contains_key(&PathBuf::from("a")));
+    }
+
+    #[test]
+    fn peek_does_not_increment_access_count() {
+        let mut cache = inner(100);
+        cache.store(PathBuf::from("a"), sized_file(10)).unwrap();
+        cache.get(&PathBuf::from("a")); // One genuine access.
+        let count_before = *cache.access_count_map.get(&PathBuf::from("a")).unwrap();
+
+        cache.peek(&PathBuf::from("a"));
+        cache.peek(&PathBuf::from("a"));
+
+        let count_after = *cache.access_count_map.get(&PathBuf::from("a")).unwrap();
+        assert_eq!(count_before, count_after);
+    }
+}
+
+/// Reads back an index written by `CacheInner::persist_index`, yielding each
+/// entry's path and access count. Lines that don't parse are skipped; a missing
+/// or unreadable index file is an error the caller can choose to ignore.
+pub(crate) fn load_persisted_index<P: AsRef<Path>>(path: P) -> io::Result<Vec<(PathBuf, usize)>> {
+    let contents = fs::read_to_string(path)?;
+    let entries = contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            parts.next()?.parse::<usize>().ok()?; // size, recorded for readability, not needed to restore.
+            let count: usize = parts.next()?.parse().ok()?;
+            let path = PathBuf::from(parts.next()?);
+            Some((path, count))
+        })
+        .collect();
+    Ok(entries)
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::usize;
+
+use priority_queue::PriorityQueue;
+
+use cache::{load_persisted_index, Cache, CacheInner};
+use freshness::Freshness;
+use priority_function::{default_priority_function, PriorityFunction};
+use sized_file::SizedFile;
+
+/// Constructs a `Cache` with a chosen byte budget, size bounds, priority function,
+/// freshness mode, and mmap/persistence behavior.
+pub struct CacheBuilder {
+    size_limit: usize,
+    min_file_size: usize,
+    max_file_size: usize,
+    priority_function: PriorityFunction,
+    freshness: Freshness,
+    mmap_threshold: usize,
+    restore_index_path: Option<PathBuf>,
+}
+
+impl CacheBuilder {
+    /// Starts a new builder with a 20 MB size limit, no size bounds, `Freshness::Never`,
+    /// and mmap disabled (every file is heap-allocated).
+    pub fn new() -> CacheBuilder {
+        CacheBuilder {
+            size_limit: 1024 * 1024 * 20,
+            min_file_size: 0,
+            max_file_size: usize::MAX,
+            priority_function: default_priority_function,
+            freshness: Freshness::Never,
+            mmap_threshold: usize::MAX,
+            restore_index_path: None,
+        }
+    }
+
+    /// Sets the total number of bytes the cache is allowed to hold.
+    pub fn size_limit_bytes(mut self, size_limit: usize) -> CacheBuilder {
+        self.size_limit = size_limit;
+        self
+    }
+
+    /// Sets the smallest file size, in bytes, that the cache will store.
+    pub fn min_file_size(mut self, min_file_size: usize) -> CacheBuilder {
+        self.min_file_size = min_file_size;
+        self
+    }
+
+    /// Sets the largest file size, in bytes, that the cache will store.
+    pub fn max_file_size(mut self, max_file_size: usize) -> CacheBuilder {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Sets the function used to score resident files when deciding what to evict.
+    pub fn priority_function(mut self, priority_function: PriorityFunction) -> CacheBuilder {
+        self.priority_function = priority_function;
+        self
+    }
+
+    /// Sets how eagerly cached files are checked against the filesystem for changes.
+    pub fn freshness(mut self, freshness: Freshness) -> CacheBuilder {
+        self.freshness = freshness;
+        self
+    }
+
+    /// Sets the file size, in bytes, above which a file is memory-mapped instead of
+    /// being copied onto the heap.
+    ///
+    /// Memory-mapped files cannot be combined with any `Freshness` other than
+    /// `Freshness::Never`: a freshness-triggered refresh (or a concurrent external
+    /// truncation) can shrink the file on disk out from under a stale mapping, and
+    /// slicing a mapped `SizedFile` past the new EOF crashes the whole process with
+    /// SIGBUS rather than failing just one request. `CacheBuilder::build()` rejects
+    /// that combination.
+    pub fn mmap_threshold(mut self, mmap_threshold: usize) -> CacheBuilder {
+        self.mmap_threshold = mmap_threshold;
+        self
+    }
+
+    /// On `build()`, re-opens and re-caches the files listed in the index previously
+    /// written by `Cache::persist_index` at `index_path`, restoring their access
+    /// counts (and so their eviction priorities) without a cold start.
+    pub fn restore_from_index<P: Into<PathBuf>>(mut self, index_path: P) -> CacheBuilder {
+        self.restore_index_path = Some(index_path.into());
+        self
+    }
+
+    /// Builds the `Cache`.
+    pub fn build(self) -> Result<Cache, String> {
+        if self.min_file_size > self.max_file_size {
+            return Err(String::from("min_file_size cannot be greater than max_file_size"));
+        }
+
+        if self.mmap_threshold != usize::MAX && self.freshness != Freshness::Never {
+            return Err(String::from(
+                "mmap_threshold cannot be combined with a Freshness other than Never: \
+                 a refresh or external truncation of a mapped file can SIGBUS the process",
+            ));
+        }
+
+        let mut inner = CacheInner {
+            size_limit: self.size_limit,
+            min_file_size: self.min_file_size,
+            max_file_size: self.max_file_size,
+            priority_function: self.priority_function,
+            freshness: self.freshness,
+            mmap_threshold: self.mmap_threshold,
+            size_bytes: 0,
+            file_map: HashMap::new(),
+            access_count_map: HashMap::new(),
+            last_checked_map: HashMap::new(),
+            priority_queue: PriorityQueue::new(),
+        };
+
+        if let Some(index_path) = self.restore_index_path {
+            if let Ok(entries) = load_persisted_index(&index_path) {
+                for (path, access_count) in entries {
+                    if let Ok(sized_file) = SizedFile::open(&path, inner.mmap_threshold) {
+                        let size = sized_file.size;
+                        if size < inner.min_file_size || size > inner.max_file_size {
+                            continue; // No longer meets the configured size bounds.
+                        }
+                        if inner.size_bytes + size > inner.size_limit {
+                            continue; // Respect the byte budget even while restoring.
+                        }
+                        inner.access_count_map.insert(path.clone(), access_count);
+                        inner.insert(path, Arc::new(sized_file));
+                    }
+                }
+            }
+        }
+
+        Ok(Cache::from_inner(inner))
+    }
+}
@@ -0,0 +1,241 @@
+use std::path::PathBuf;
+use std::result;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::{Response, Responder};
+
+use sized_file::SizedFile;
+
+/// A handle to a file that lives in the `Cache`.
+#[derive(Debug, Clone)]
+pub struct CachedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) file: Arc<SizedFile>,
+}
+
+/// Streams the named file to the client. Sets or overrides the Content-Type in
+/// the response according to the file's extension if the extension is
+/// recognized. See
+/// [ContentType::from_extension](/rocket/http/struct.ContentType.html#method.from_extension)
+/// for more information. If you would like to stream a file with a different
+/// Content-Type than that implied by its extension, use a `File` directly.
+///
+/// Since the whole file already lives in memory, this also understands `Range`
+/// requests (206, one `bytes=` range per request) and conditional requests
+/// (`If-Modified-Since`/`If-None-Match`, answered with 304 and no body).
+impl Responder<'static> for CachedFile {
+    fn respond_to(self, request: &Request) -> result::Result<Response<'static>, Status> {
+        let mut response = Response::new();
+        set_common_headers(&mut response, &self.path, &self.file);
+
+        if is_not_modified(request, &self.file) {
+            response.set_status(Status::NotModified);
+            return Ok(response);
+        }
+
+        let len = self.file.size;
+        let range = request
+            .headers()
+            .get_one("Range")
+            .filter(|header| header.trim().starts_with("bytes="))
+            .map(|header| parse_range(header, len));
+
+        let file: *const SizedFile = Arc::into_raw(self.file);
+        unsafe {
+            set_ranged_body(&mut response, (*file).as_bytes(), len, range);
+            let _ = Arc::from_raw(file); // Prevent dangling pointer?
+        }
+
+        Ok(response)
+    }
+}
+
+/// Alternative implementation for sending the file via a reference.
+impl<'a> Responder<'a> for &'a CachedFile {
+    fn respond_to(self, request: &Request) -> result::Result<Response<'a>, Status> {
+        let mut response = Response::new();
+        set_common_headers(&mut response, &self.path, &self.file);
+
+        if is_not_modified(request, &self.file) {
+            response.set_status(Status::NotModified);
+            return Ok(response);
+        }
+
+        let len = self.file.size;
+        let range = request
+            .headers()
+            .get_one("Range")
+            .filter(|header| header.trim().starts_with("bytes="))
+            .map(|header| parse_range(header, len));
+        set_ranged_body(&mut response, self.file.as_bytes(), len, range);
+
+        Ok(response)
+    }
+}
+
+fn set_common_headers(response: &mut Response, path: &PathBuf, file: &SizedFile) {
+    if let Some(ext) = path.extension() {
+        if let Some(ct) = ContentType::from_extension(&ext.to_string_lossy()) {
+            response.set_header(ct);
+        }
+    }
+    response.set_raw_header("Accept-Ranges", "bytes");
+    response.set_raw_header("Last-Modified", httpdate::fmt_http_date(file.modified));
+    response.set_raw_header("ETag", etag(file));
+}
+
+fn set_ranged_body<'r>(response: &mut Response<'r>, bytes: &'r [u8], len: usize, range: Option<Result<(usize, usize), ()>>) {
+    match range {
+        Some(Ok((start, end))) => {
+            response.set_status(Status::PartialContent);
+            response.set_raw_header("Content-Range", format!("bytes {}-{}/{}", start, end, len));
+            response.set_streamed_body(&bytes[start..=end]);
+        }
+        Some(Err(())) => {
+            response.set_status(Status::RangeNotSatisfiable);
+            response.set_raw_header("Content-Range", format!("bytes */{}", len));
+        }
+        None => {
+            response.set_streamed_body(bytes);
+        }
+    }
+}
+
+/// An ETag derived from the file's size and modified-time, good enough to tell
+/// two versions of the same cached file apart without hashing the contents.
+fn etag(file: &SizedFile) -> String {
+    let mtime_secs = file
+        .modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", file.size, mtime_secs)
+}
+
+fn is_not_modified(request: &Request, file: &SizedFile) -> bool {
+    if let Some(if_none_match) = request.headers().get_one("If-None-Match") {
+        let current = etag(file);
+        if if_none_match.split(',').any(|tag| tag.trim() == current || tag.trim() == "*") {
+            return true;
+        }
+    }
+
+    if let Some(if_modified_since) = request.headers().get_one("If-Modified-Since") {
+        if let Ok(since) = httpdate::parse_http_date(if_modified_since) {
+            // Last-Modified is sent with whole-second precision, so compare at that precision.
+            let secs = |time: SystemTime| {
+                time.duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0)
+            };
+            if secs(file.modified) <= secs(since) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Parses a `Range` header's first `bytes=` range against a resource of length `len`.
+///
+/// Returns `Err(())` if the range is malformed or unsatisfiable for `len`, and
+/// `Ok((start, end))` (inclusive bounds) otherwise. Supports `start-end`, open-ended
+/// `start-`, and suffix `-n` forms; only the first range of a multi-range request is
+/// honored. The responders only pass headers in the `bytes` unit; a `Range` header
+/// in any other unit is ignored and the full file is sent.
+fn parse_range(header: &str, len: usize) -> Result<(usize, usize), ()> {
+    let spec = header.trim().trim_start_matches("bytes=");
+    let spec = spec.split(',').next().unwrap_or("").trim();
+
+    if len == 0 {
+        return Err(());
+    }
+
+    if spec.starts_with('-') {
+        let n: usize = spec[1..].parse().map_err(|_| ())?;
+        if n == 0 {
+            return Err(());
+        }
+        let start = len.saturating_sub(n);
+        return Ok((start, len - 1));
+    }
+
+    let mut parts = spec.splitn(2, '-');
+    let start: usize = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+    let end_str = parts.next().ok_or(())?;
+
+    if start >= len {
+        return Err(());
+    }
+
+    let end = if end_str.is_empty() {
+        len - 1
+    } else {
+        let requested_end: usize = end_str.parse().map_err(|_| ())?;
+        if requested_end < start {
+            return Err(());
+        }
+        requested_end.min(len - 1)
+    };
+
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_end() {
+        assert_eq!(parse_range("bytes=0-99", 200), Ok((0, 99)));
+    }
+
+    #[test]
+    fn open_ended() {
+        assert_eq!(parse_range("bytes=100-", 200), Ok((100, 199)));
+    }
+
+    #[test]
+    fn suffix() {
+        assert_eq!(parse_range("bytes=-50", 200), Ok((150, 199)));
+    }
+
+    #[test]
+    fn suffix_larger_than_file_clamps_to_start() {
+        assert_eq!(parse_range("bytes=-500", 200), Ok((0, 199)));
+    }
+
+    #[test]
+    fn end_past_eof_is_clamped() {
+        assert_eq!(parse_range("bytes=0-500", 200), Ok((0, 199)));
+    }
+
+    #[test]
+    fn start_at_or_past_len_is_unsatisfiable() {
+        assert_eq!(parse_range("bytes=200-", 200), Err(()));
+    }
+
+    #[test]
+    fn end_before_start_is_unsatisfiable() {
+        assert_eq!(parse_range("bytes=50-10", 200), Err(()));
+    }
+
+    #[test]
+    fn zero_length_suffix_is_unsatisfiable() {
+        assert_eq!(parse_range("bytes=-0", 200), Err(()));
+    }
+
+    #[test]
+    fn any_range_against_empty_file_is_unsatisfiable() {
+        assert_eq!(parse_range("bytes=0-0", 0), Err(()));
+    }
+
+    #[test]
+    fn only_the_first_range_of_a_multi_range_request_is_honored() {
+        assert_eq!(parse_range("bytes=0-9,20-29", 200), Ok((0, 9)));
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Indicates how a file ended up getting stored in the `Cache`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheInvalidationSuccess {
+    /// The file was inserted into space that was already free in the cache.
+    InsertedIntoAvailableSpace,
+    /// One or more lower-priority files were evicted from the cache to make room for the new file.
+    ReplacedFile,
+}
+
+/// Indicates why a file could not be stored in the `Cache`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheInvalidationError {
+    /// The file is larger than the cache's entire size limit, so it could never be stored.
+    NewFileLargerThanCache,
+    /// The file is smaller than the `CacheBuilder`-configured minimum file size.
+    NewFileSmallerThanMin,
+    /// The file is larger than the `CacheBuilder`-configured maximum file size.
+    NewFileLargerThanMax,
+    /// Evicting the lowest-priority files resident in the cache would not free up enough
+    /// priority to justify displacing them for the new file.
+    NewPriorityIsNotHighEnough,
+}
+
+impl fmt::Display for CacheInvalidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CacheInvalidationError::NewFileLargerThanCache => {
+                write!(f, "the file is larger than the cache's size limit")
+            }
+            CacheInvalidationError::NewFileSmallerThanMin => {
+                write!(f, "the file is smaller than the cache's minimum file size")
+            }
+            CacheInvalidationError::NewFileLargerThanMax => {
+                write!(f, "the file is larger than the cache's maximum file size")
+            }
+            CacheInvalidationError::NewPriorityIsNotHighEnough => write!(
+                f,
+                "the file's priority is not high enough to evict the files that would need to be removed to make room for it"
+            ),
+        }
+    }
+}
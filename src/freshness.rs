@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+/// Controls how eagerly a cached file is checked against the filesystem for changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Freshness {
+    /// Never re-check; once cached, a file is served as-is until the process restarts.
+    Never,
+    /// Stat the path on every hit and reload the file if its mtime has changed.
+    Always,
+    /// Stat the path at most once per `Duration`, per path.
+    Interval(Duration),
+}
+
+impl Default for Freshness {
+    fn default() -> Freshness {
+        Freshness::Never
+    }
+}
@@ -0,0 +1,23 @@
+#![feature(plugin)]
+
+extern crate rocket;
+#[macro_use]
+extern crate log;
+extern crate httpdate;
+extern crate memmap2;
+extern crate priority_queue;
+
+pub mod cache;
+pub mod cache_builder;
+pub mod cached_file;
+pub mod error;
+pub mod freshness;
+pub mod priority_function;
+pub mod sized_file;
+
+pub use cache::Cache;
+pub use cache_builder::CacheBuilder;
+pub use cached_file::CachedFile;
+pub use error::{CacheInvalidationError, CacheInvalidationSuccess};
+pub use freshness::Freshness;
+pub use priority_function::PriorityFunction;
@@ -19,11 +19,16 @@ use rocket::response::Redirect;
 use rocket::Rocket;
 use std::path::{Path, PathBuf};
 use rocket::request::State;
-use std::sync::Mutex;
 use std::fs::File;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use simplelog::{Config, TermLogger, WriteLogger, CombinedLogger, LogLevelFilter};
 
+/// Where the cache's resident-file index is persisted between runs.
+const CACHE_INDEX_PATH: &'static str = "cache_index.tsv";
+
 
 #[get("/")]
 fn index() -> Redirect {
@@ -45,22 +50,31 @@ fn main() {
 }
 
 fn init_rocket() -> Rocket {
-    let cache: Mutex<Cache> = Mutex::new(
+    let cache: Arc<Cache> = Arc::new(
         CacheBuilder::new()
             .size_limit_bytes(1024 * 1024 * 20) // 20 MB limit
+            .restore_from_index(CACHE_INDEX_PATH)
             .build()
-            .unwrap()
+            .unwrap(),
     );
 
+    let persisted_cache = cache.clone();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(60));
+        if let Err(e) = persisted_cache.persist_index(CACHE_INDEX_PATH) {
+            warn!("Failed to persist cache index: {}", e);
+        }
+    });
+
     rocket::ignite()
         .manage(cache)
         .mount("/", routes![homepage_files, index])
 }
 
 #[get("/<path..>", rank=4)]
-fn homepage_files(path: PathBuf, cache: State<Mutex<Cache>>) -> Option<CachedFile> {
+fn homepage_files(path: PathBuf, cache: State<Arc<Cache>>) -> Option<CachedFile> {
     let pathbuf: PathBuf = Path::new("www/").join(path.clone()).to_owned();
-    cache.lock().unwrap().get_or_cache(pathbuf)
+    cache.get_or_cache(pathbuf)
 }
 
 
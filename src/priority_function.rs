@@ -0,0 +1,13 @@
+/// Scores a cached file so the `Cache` can decide what to evict first.
+///
+/// Files with a lower priority are evicted before files with a higher priority.
+pub type PriorityFunction = fn(access_count: usize, size_bytes: usize) -> usize;
+
+/// The default `PriorityFunction`.
+///
+/// Multiplying the access count by the size means that a file that is accessed
+/// frequently is considered valuable even if it is large, while a huge file that
+/// has only been read once can't dominate the cache on size alone.
+pub fn default_priority_function(access_count: usize, size_bytes: usize) -> usize {
+    access_count * size_bytes
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+use memmap2::Mmap;
+
+/// The in-memory backing for a cached file: either an owned byte buffer, or a
+/// memory-mapped view of the file on disk.
+pub(crate) enum FileBacking {
+    Heap(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl FileBacking {
+    fn as_slice(&self) -> &[u8] {
+        match *self {
+            FileBacking::Heap(ref bytes) => bytes.as_slice(),
+            FileBacking::Mapped(ref mmap) => &mmap[..],
+        }
+    }
+}
+
+/// A file that has been read into memory (or memory-mapped), along with its
+/// size in bytes and the filesystem modified-time it was read with.
+pub struct SizedFile {
+    pub(crate) backing: FileBacking,
+    pub(crate) size: usize,
+    pub(crate) modified: SystemTime,
+}
+
+impl SizedFile {
+    /// Reads the file at `path`. Files larger than `mmap_threshold` bytes are
+    /// memory-mapped instead of being copied onto the heap.
+    pub fn open<P: AsRef<Path>>(path: P, mmap_threshold: usize) -> io::Result<SizedFile> {
+        let file = File::open(path.as_ref())?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified()?;
+
+        let (backing, size) = if metadata.len() as usize > mmap_threshold {
+            let mmap = unsafe { Mmap::map(&file)? };
+            let size = mmap.len();
+            (FileBacking::Mapped(mmap), size)
+        } else {
+            let mut reader = BufReader::new(file);
+            let mut buffer: Vec<u8> = vec![];
+            reader.read_to_end(&mut buffer)?;
+            let size = buffer.len();
+            (FileBacking::Heap(buffer), size)
+        };
+
+        Ok(SizedFile {
+            backing,
+            size,
+            modified,
+        })
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        self.backing.as_slice()
+    }
+}
+
+impl fmt::Debug for SizedFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SizedFile")
+            .field("size", &self.size)
+            .field("modified", &self.modified)
+            .finish()
+    }
+}